@@ -0,0 +1,153 @@
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use reqwest::Client;
+
+use crate::{Result, Token};
+
+/// Supplies a valid `token2` for authenticating requests against the Star Realms API.
+///
+/// Implementations own their own credentials/cache. Those that hold login credentials (such as
+/// [`PasswordProvider`]) re-authenticate once a previously issued token goes stale, so callers can
+/// simply ask for [`TokenProvider::token`] before every request instead of tracking expiry themselves.
+/// Providers built from an existing token ([`StaticToken2Provider`], [`FullTokenProvider`]) always
+/// return that token and do not re-authenticate.
+#[async_trait]
+pub trait TokenProvider: std::fmt::Debug + Send {
+    /// Returns a `token2`, re-logging in first if the provider can and its cached one has expired.
+    async fn token(&mut self, client: &Client) -> Result<String>;
+
+    /// Returns the full [`Token`] last obtained by this provider (username, purchases, etc.),
+    /// if it has one cached. Providers that only ever see a bare `token2` return `None`.
+    fn token_data(&self) -> Option<&Token> {
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CachedToken {
+    token: Token,
+    issued_at: Instant,
+}
+
+impl CachedToken {
+    fn is_stale(&self) -> bool {
+        match self.token.expires_in {
+            Some(expires_in) => self.issued_at.elapsed() >= Duration::from_secs(expires_in),
+            None => false,
+        }
+    }
+}
+
+/// Logs in with a username and password, lazily re-logging in once the token expires.
+/// This is the provider used by [`crate::StarRealms::new`]. The password is retained in memory
+/// for the lifetime of the provider so it can re-login once the cached token goes stale.
+#[derive(Clone)]
+pub struct PasswordProvider {
+    username: String,
+    password: String,
+    cached: Option<CachedToken>,
+}
+
+impl PasswordProvider {
+    pub fn new(username: impl Into<String>, password: impl Into<String>) -> Self {
+        PasswordProvider {
+            username: username.into(),
+            password: password.into(),
+            cached: None,
+        }
+    }
+}
+
+impl std::fmt::Debug for PasswordProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PasswordProvider")
+            .field("username", &self.username)
+            .field("password", &"[redacted]")
+            .field("cached", &self.cached)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl TokenProvider for PasswordProvider {
+    async fn token(&mut self, client: &Client) -> Result<String> {
+        if let Some(cached) = &self.cached {
+            if !cached.is_stale() {
+                return Ok(cached.token.token2.clone());
+            }
+        }
+        let params = [
+            ("username", self.username.as_str()),
+            ("password", self.password.as_str()),
+        ];
+        let res = crate::error::send_with_retry(|| {
+            client
+                .post("https://srprodv2.whitewizardgames.com/Account/Login")
+                .form(&params)
+        })
+        .await?;
+        let token: Token = res.json().await?;
+        let token2 = token.token2.clone();
+        self.cached = Some(CachedToken {
+            token,
+            issued_at: Instant::now(),
+        });
+        Ok(token2)
+    }
+
+    fn token_data(&self) -> Option<&Token> {
+        self.cached.as_ref().map(|cached| &cached.token)
+    }
+}
+
+/// Wraps an already-known `token2`, skipping login entirely.
+/// As we never log in, other data normally returned alongside a token (such as purchases) is unavailable.
+#[derive(Debug, Clone)]
+pub struct StaticToken2Provider {
+    token2: String,
+}
+
+impl StaticToken2Provider {
+    pub fn new(token2: impl Into<String>) -> Self {
+        StaticToken2Provider {
+            token2: token2.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl TokenProvider for StaticToken2Provider {
+    async fn token(&mut self, _client: &Client) -> Result<String> {
+        Ok(self.token2.clone())
+    }
+}
+
+/// Wraps a previously obtained [`Token`], as returned by a prior login.
+/// Since we don't retain the original credentials, this provider cannot re-login on its own.
+#[derive(Debug, Clone)]
+pub struct FullTokenProvider {
+    token: Token,
+}
+
+impl FullTokenProvider {
+    pub fn new(token: Token) -> Self {
+        FullTokenProvider { token }
+    }
+
+    /// Returns the full [`Token`] this provider was constructed with.
+    pub fn token_data(&self) -> &Token {
+        &self.token
+    }
+}
+
+#[async_trait]
+impl TokenProvider for FullTokenProvider {
+    async fn token(&mut self, _client: &Client) -> Result<String> {
+        Ok(self.token.token2.clone())
+    }
+
+    fn token_data(&self) -> Option<&Token> {
+        Some(&self.token)
+    }
+}
@@ -1,3 +1,6 @@
+use std::time::Duration;
+
+use reqwest::{RequestBuilder, StatusCode};
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -10,8 +13,73 @@ pub enum Error {
     InvalidAPIResponse(String),
     #[error("Unknown Player Name: {0}")]
     InvalidPlayerName(String),
-    #[error("Unknown Core Version")]
-    UnknownCoreVersion(),
+    #[error("No core version accepted by the server")]
+    CoreVersionRejected,
+    #[error("Authentication rejected by server")]
+    Unauthorized,
+    #[error("Rate limited by server")]
+    RateLimited { retry_after: Option<Duration> },
+    #[error("Server error: {0}")]
+    ServerError(StatusCode),
     #[error("Unknown Star Realms Error")]
     Unknown,
-}
\ No newline at end of file
+}
+
+impl Error {
+    /// Returns true if this error represents a transient server-side condition
+    /// (rate limiting or a 5xx) that is worth retrying.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Error::RateLimited { .. } | Error::ServerError(_))
+    }
+}
+
+/// Classifies a non-200 response status into a structured [`Error`] variant.
+pub(crate) fn error_for_status(status: StatusCode, retry_after: Option<Duration>) -> Error {
+    match status {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Error::Unauthorized,
+        StatusCode::TOO_MANY_REQUESTS => Error::RateLimited { retry_after },
+        status if status.is_server_error() => Error::ServerError(status),
+        status => Error::InvalidAPIResponse(status.to_string()),
+    }
+}
+
+/// Parses the `Retry-After` header (in seconds) off a response, if present.
+pub(crate) fn parse_retry_after(res: &reqwest::Response) -> Option<Duration> {
+    res.headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok())
+        .map(Duration::from_secs)
+}
+
+const MAX_ATTEMPTS: u32 = 3;
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Sends the request built by `build` (called once per attempt), retrying with exponential
+/// backoff while the response classifies as [`Error::is_retryable`].
+pub(crate) async fn send_with_retry(
+    build: impl Fn() -> RequestBuilder,
+) -> Result<reqwest::Response> {
+    let mut delay = INITIAL_BACKOFF;
+    let mut last_err = None;
+    for _ in 0..MAX_ATTEMPTS {
+        let res = build().send().await?;
+        if res.status() == StatusCode::OK {
+            return Ok(res);
+        }
+        let err = error_for_status(res.status(), parse_retry_after(&res));
+        if !err.is_retryable() {
+            return Err(err);
+        }
+        let sleep_for = match &err {
+            Error::RateLimited {
+                retry_after: Some(retry_after),
+            } => delay.max(*retry_after),
+            _ => delay,
+        };
+        last_err = Some(err);
+        tokio::time::sleep(sleep_for).await;
+        delay *= 2;
+    }
+    Err(last_err.unwrap_or(Error::Unknown))
+}
@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+
+/// The full in-progress state of a single game, as returned by the server.
+/// Unlike [`crate::Game`] (the activity-list summary), this includes the actual board.
+#[derive(Debug, Deserialize, Clone)]
+pub struct GameState {
+    #[serde(rename = "gameid")]
+    pub id: i64,
+    pub turn: i64,
+    pub board: ::serde_json::Value, //TODO: Find what this is
+    pub hand: Vec<::serde_json::Value>, //TODO: Find what this is
+    pub result: String,
+}
+
+/// A single turn action a player can submit against an in-progress game.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "action", content = "data")]
+pub enum GameAction {
+    PlayCard(u64),
+    BuyCard(u64),
+    Attack(u64),
+    EndTurn,
+    Concede,
+}
@@ -1,110 +1,281 @@
+pub use self::auth::{FullTokenProvider, PasswordProvider, StaticToken2Provider, TokenProvider};
 pub use self::error::{Error, Result};
+pub use self::game::{GameAction, GameState};
+pub use self::mock::MockStarRealms;
+use async_trait::async_trait;
+use chrono::{DateTime, TimeZone, Utc};
+use error::send_with_retry;
+use futures::future::join_all;
 use log::info;
 use reqwest::Client;
 use serde::{Deserialize, de};
 
+mod auth;
 mod error; //TODO: Rename
+mod game;
+mod mock;
+
+/// Lowest core version ever seen accepted by the server; the start of the probing range.
+const CORE_VERSION_MIN: usize = 45;
+/// Upper bound of the probing range used by [`StarRealms::find_core_version`].
+const CORE_VERSION_MAX: usize = 100;
 
 /// A single logged in instance of a logged in Star Realms user
-#[derive(Debug, Clone)]
 pub struct StarRealms {
-    pub token: Token,
+    provider: Box<dyn TokenProvider>,
     core_version: usize,
     client: Client,
 }
 
-
 impl StarRealms {
     /// Create a new instance of StarRealms using a user's Username and Password to login.
-    /// Password is not retained internally and is sent via HTTPS connection to official Star Realms servers
+    /// The password is sent via HTTPS connection to official Star Realms servers and is retained
+    /// in-memory by the underlying [`PasswordProvider`] for the life of this instance, so it can
+    /// transparently re-login once the cached token expires.
     pub async fn new(username: &str, password: &str) -> Result<StarRealms> {
-        let mut sr = StarRealms {
-            token: Token::default(),
-            core_version: 45,
-            client: reqwest::Client::new(),
-        };
-        sr.new_token(username, password).await?;
-        sr.find_core_version().await?;
-        Ok(sr)
+        StarRealms::with_provider(Box::new(PasswordProvider::new(username, password))).await
     }
 
     /// Create a new instance of StarRealms using a user's token. The required token is Token2 from the token response from the server.
     /// As we don't get a token, we also don't have other data available that is usually provided when retrieving a token, such as purchases.
     pub async fn new_with_token2_str(token: &str) -> Result<StarRealms> {
-        let mut sr = StarRealms {
-            token: Token::default(),
-            core_version: 45,
-            client: reqwest::Client::new(),
-        };
-        sr.token.token2 = token.to_string();
-        sr.find_core_version().await?;
-        Ok(sr)
+        StarRealms::with_provider(Box::new(StaticToken2Provider::new(token))).await
     }
 
     /// Create a new instance of StarRealms using a previously made Token.
     pub async fn new_with_token(token: Token) -> Result<StarRealms> {
+        StarRealms::with_provider(Box::new(FullTokenProvider::new(token))).await
+    }
+
+    /// Create a new instance of StarRealms using a custom [`TokenProvider`], letting callers
+    /// plug in their own credential store instead of one of the built-in login flows.
+    pub async fn with_provider(provider: Box<dyn TokenProvider>) -> Result<StarRealms> {
+        StarRealms::with_provider_and_core_version_hint(provider, None).await
+    }
+
+    /// Create a new instance of StarRealms using a custom [`TokenProvider`], and, if already known
+    /// (e.g. from a previous run's [`StarRealms::core_version`]), a core version hint that skips
+    /// version discovery entirely.
+    pub async fn with_provider_and_core_version_hint(
+        provider: Box<dyn TokenProvider>,
+        core_version_hint: Option<usize>,
+    ) -> Result<StarRealms> {
         let mut sr = StarRealms {
-            token: token,
-            core_version: 45,
+            provider,
+            core_version: CORE_VERSION_MIN,
             client: reqwest::Client::new(),
         };
-        sr.find_core_version().await?;
+        match core_version_hint {
+            Some(core_version) => sr.core_version = core_version,
+            None => sr.find_core_version().await?,
+        }
         Ok(sr)
     }
 
-    /// Gets a login token using the username and password.
-    /// This token doesn't seem to expire
-    async fn new_token(&mut self, username: &str, password: &str) -> Result<()> {
-        let params = [("username", username), ("password", password)];
-        let res = self
-            .client
-            .post("https://srprodv2.whitewizardgames.com/Account/Login")
-            .form(&params)
-            .send()
-            .await?;
-        if res.status() != 200 {
-            return Err(Error::InvalidAPIResponse(res.status().to_string()));
+    /// Gets a valid `token2`, asking the [`TokenProvider`] to re-login first if its cached token has gone stale.
+    async fn token(&mut self) -> Result<String> {
+        self.provider.token(&self.client).await
+    }
+
+    /// Returns the full [`Token`] the underlying [`TokenProvider`] last obtained (username,
+    /// purchases, etc.), if it has one cached. `None` for providers that only ever see a bare
+    /// `token2`, such as [`StaticToken2Provider`].
+    pub fn token_data(&self) -> Option<&Token> {
+        self.provider.token_data()
+    }
+
+    /// Returns the core version this instance discovered (or was given as a hint).
+    pub fn core_version(&self) -> usize {
+        self.core_version
+    }
+
+    /// Checks whether a given core version is currently accepted by the server.
+    /// Checks whether a given core version is accepted by the server. Auth failures and
+    /// rate-limit/server errors are propagated rather than treated as a rejected version,
+    /// since they have nothing to do with the version number being probed.
+    async fn probe_core_version(client: &Client, token: &str, core_version: usize) -> Result<bool> {
+        let res = send_with_retry(|| {
+            client
+                .get("https://srprodv2.whitewizardgames.com/NewGame/ListActivitySortable")
+                .header("Auth", token)
+                .header("coreversion", core_version)
+        })
+        .await;
+        match res {
+            Ok(_) => Ok(true),
+            // A plain rejection just means this core version is wrong, try another.
+            // `send_with_retry` has already retried anything transient (rate-limit/5xx).
+            Err(Error::InvalidAPIResponse(_)) => Ok(false),
+            Err(err) => Err(err),
         }
-        self.token = res.json().await?;
-        Ok(())
     }
 
-    /// Get the latest core version via trial and error
+    /// Get the latest core version by probing a bounded batch of candidate versions concurrently
+    /// and picking the highest one the server accepts.
     /// Incorrect core version causes empty or invalid responses for other calls
     async fn find_core_version(&mut self) -> Result<()> {
-        //TODO: Improve, as maybe multiple core versions are needed
-        for core_version in 45..100 {
-            let res = self
-                .client
-                .get("https://srprodv2.whitewizardgames.com/NewGame/ListActivitySortable")
-                .header("Auth", &self.token.token2)
-                .header("coreversion", core_version)
-                .send()
-                .await?;
-            if res.status() == 200 {
+        const BATCH_SIZE: usize = 8;
+        let token = self.token().await?;
+        let candidates: Vec<usize> = (CORE_VERSION_MIN..CORE_VERSION_MAX).collect();
+        let mut found = None;
+        for batch in candidates.chunks(BATCH_SIZE) {
+            let results: Result<Vec<Option<usize>>> = join_all(batch.iter().map(|&core_version| {
+                let client = self.client.clone();
+                let token = token.clone();
+                async move {
+                    let accepted =
+                        StarRealms::probe_core_version(&client, &token, core_version).await?;
+                    Ok(accepted.then_some(core_version))
+                }
+            }))
+            .await
+            .into_iter()
+            .collect();
+            if let Some(version) = results?.into_iter().flatten().max() {
+                found = Some(found.map_or(version, |best: usize| best.max(version)));
+            }
+        }
+        match found {
+            Some(core_version) => {
                 self.core_version = core_version;
                 info!("Found core version: {}", self.core_version);
-                return Ok(());
+                Ok(())
+            }
+            None => Err(Error::CoreVersionRejected),
+        }
+    }
+
+    /// Get the latest core version using an exponential probe upward from `start_hint`
+    /// (defaulting to [`CORE_VERSION_MIN`]) followed by a binary search of the accepted/rejected
+    /// boundary. Since any version at or above the server's current release tends to succeed,
+    /// this turns the O(n) scan in [`StarRealms::find_core_version`] into O(log n) requests.
+    pub async fn find_core_version_binsearch(&mut self, start_hint: Option<usize>) -> Result<()> {
+        let token = self.token().await?;
+        let mut low = start_hint.unwrap_or(CORE_VERSION_MIN);
+        if StarRealms::probe_core_version(&self.client, &token, low).await? {
+            self.core_version = low;
+            info!("Found core version: {}", self.core_version);
+            return Ok(());
+        }
+
+        let mut step = 1;
+        let mut high = low + step;
+        while !StarRealms::probe_core_version(&self.client, &token, high).await? {
+            if high > CORE_VERSION_MAX * 4 {
+                return Err(Error::CoreVersionRejected);
             }
+            low = high;
+            step *= 2;
+            high += step;
         }
-        Err(Error::UnknownCoreVersion())
-    }
-
-    /// Get the latest user activity, including current player data
-    pub async fn activity(&self) -> Result<Activity> {
-        let res = self
-            .client
-            .get("https://srprodv2.whitewizardgames.com/NewGame/ListActivitySortable")
-            .header("Auth", &self.token.token2)
-            .header("coreversion", self.core_version)
-            .send()
-            .await?;
-        if res.status() != 200 {
-            return Err(Error::InvalidAPIResponse(res.status().to_string()));
+
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            if StarRealms::probe_core_version(&self.client, &token, mid).await? {
+                high = mid;
+            } else {
+                low = mid;
+            }
         }
+
+        self.core_version = high;
+        info!("Found core version: {}", self.core_version);
+        Ok(())
+    }
+
+    /// Get the latest user activity, including current player data.
+    /// Transient rate-limit/server errors are retried with exponential backoff.
+    pub async fn activity(&mut self) -> Result<Activity> {
+        let token = self.token().await?;
+        let client = &self.client;
+        let core_version = self.core_version;
+        let res = send_with_retry(|| {
+            client
+                .get("https://srprodv2.whitewizardgames.com/NewGame/ListActivitySortable")
+                .header("Auth", &token)
+                .header("coreversion", core_version)
+        })
+        .await?;
+        Ok(res.json().await?)
+    }
+
+    /// Fetch the full in-progress board state of a game. `auth` is the per-player auth value
+    /// obtained from that game's [`ClientData::get_auth`].
+    pub async fn game_state(&mut self, game_id: i64, auth: isize) -> Result<GameState> {
+        let token = self.token().await?;
+        let client = &self.client;
+        let core_version = self.core_version;
+        let res = send_with_retry(|| {
+            client
+                .get("https://srprodv2.whitewizardgames.com/NewGame/GetGame")
+                .header("Auth", &token)
+                .header("coreversion", core_version)
+                .query(&[("gameid", game_id.to_string()), ("auth", auth.to_string())])
+        })
+        .await?;
         Ok(res.json().await?)
     }
 
+    /// Submit a turn action against an in-progress game, returning the resulting board state.
+    pub async fn submit_action(
+        &mut self,
+        game_id: i64,
+        auth: isize,
+        action: GameAction,
+    ) -> Result<GameState> {
+        let token = self.token().await?;
+        let client = &self.client;
+        let core_version = self.core_version;
+        let res = send_with_retry(|| {
+            client
+                .post("https://srprodv2.whitewizardgames.com/NewGame/SubmitAction")
+                .header("Auth", &token)
+                .header("coreversion", core_version)
+                .query(&[("gameid", game_id.to_string()), ("auth", auth.to_string())])
+                .json(&action)
+        })
+        .await?;
+        Ok(res.json().await?)
+    }
+}
+
+/// The operations a Star Realms client exposes, abstracted so alternate backends (such as
+/// [`MockStarRealms`]) can stand in for the real [`StarRealms`] client, e.g. in tests.
+#[async_trait]
+pub trait StarRealmsApi {
+    /// Get the latest user activity, including current player data.
+    async fn activity(&mut self) -> Result<Activity>;
+
+    /// Fetch the full in-progress board state of a game.
+    async fn game_state(&mut self, game_id: i64, auth: isize) -> Result<GameState>;
+
+    /// Submit a turn action against an in-progress game, returning the resulting board state.
+    async fn submit_action(
+        &mut self,
+        game_id: i64,
+        auth: isize,
+        action: GameAction,
+    ) -> Result<GameState>;
+}
+
+#[async_trait]
+impl StarRealmsApi for StarRealms {
+    async fn activity(&mut self) -> Result<Activity> {
+        StarRealms::activity(self).await
+    }
+
+    async fn game_state(&mut self, game_id: i64, auth: isize) -> Result<GameState> {
+        StarRealms::game_state(self, game_id, auth).await
+    }
+
+    async fn submit_action(
+        &mut self,
+        game_id: i64,
+        auth: isize,
+        action: GameAction,
+    ) -> Result<GameState> {
+        StarRealms::submit_action(self, game_id, auth, action).await
+    }
 }
 
 //TODO: More rust friendly names?
@@ -116,9 +287,12 @@ pub struct Token {
     pub token1: String,
     pub token2: String,
     pub purchases: Vec<String>,
+    /// How many seconds this token is valid for, if the server provided one.
+    #[serde(default)]
+    pub expires_in: Option<u64>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Clone)]
 pub struct Activity {
     pub acceptedterms: bool,
     pub avatar: String,
@@ -141,7 +315,8 @@ pub struct Game {
     #[serde(rename = "gameid")]
     pub id: i64,
     pub timing: String,
-    pub mmdata: String,     //TODO: Change this into a struct
+    #[serde(deserialize_with = "deserialize_mmdata")]
+    pub mmdata: MmData,
     #[serde(deserialize_with = "deserialize_clientdata")]
     pub clientdata: ClientData,
     pub opponentname: String,
@@ -151,11 +326,47 @@ pub struct Game {
     pub endreason: i64, //TODO: Figure out what these are. 2 == concede, 0 == lost?/normal game end
     #[serde(default)]
     pub won: bool,
-    pub lastupdatedtime: String, //TODO: Change to chrono time?
+    #[serde(deserialize_with = "deserialize_datetime")]
+    pub lastupdatedtime: DateTime<Utc>,
     pub isleaguegame: bool,
     pub istournamentgame: bool,
 }
 
+/// Matchmaking metadata embedded as a stringified JSON blob in `mmdata`.
+#[derive(Debug, Deserialize, Clone)]
+pub struct MmData {
+    #[serde(default)]
+    pub ranked: bool,
+    #[serde(default)]
+    pub timing: Option<String>,
+    #[serde(default)]
+    pub deck: Option<String>,
+    #[serde(default)]
+    pub commander: Option<String>,
+}
+
+fn deserialize_mmdata<'de, D>(deserializer: D) -> std::result::Result<MmData, D::Error>
+where
+    D: de::Deserializer<'de>,
+{
+    let s: String = de::Deserialize::deserialize(deserializer)?;
+    serde_json::from_str(&s).map_err(de::Error::custom)
+}
+
+/// Parses a timestamp field (such as `lastupdatedtime`) sent either as an RFC3339 timestamp or a bare `YYYY-MM-DDTHH:MM:SS` string.
+fn deserialize_datetime<'de, D>(deserializer: D) -> std::result::Result<DateTime<Utc>, D::Error>
+where
+    D: de::Deserializer<'de>,
+{
+    let s: String = de::Deserialize::deserialize(deserializer)?;
+    if let Ok(dt) = DateTime::parse_from_rfc3339(&s) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+    chrono::NaiveDateTime::parse_from_str(&s, "%Y-%m-%dT%H:%M:%S%.f")
+        .map(|naive| Utc.from_utc_datetime(&naive))
+        .map_err(de::Error::custom)
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct ClientData {
     #[serde(rename = "p1auth")]
@@ -195,6 +406,11 @@ impl Game {
         self.endreason == 0 && !self.won && !self.actionneeded
     }
 
+    /// Returns when this game was last updated.
+    pub fn last_updated(&self) -> DateTime<Utc> {
+        self.lastupdatedtime
+    }
+
     /// Returns the name of the player whose turn it currently is
     pub fn which_turn(&self) -> String {
         let mut which_turn = self.opponentname.clone();
@@ -214,20 +430,29 @@ impl Game {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Clone)]
 pub struct Challenge {
     #[serde(rename = "challengeid")]
     pub id: i64,
     pub challengername: String,
     pub challengercommander: String,
     pub opponentname: String,
-    pub mmdata: String,
+    #[serde(deserialize_with = "deserialize_mmdata")]
+    pub mmdata: MmData,
     pub status: String, //TODO: Change to enum?
     pub statusdescription: String,
-    pub lastupdatedtime: String,
+    #[serde(deserialize_with = "deserialize_datetime")]
+    pub lastupdatedtime: DateTime<Utc>,
     pub timing: String, //TODO: Change to enum?
 }
 
+impl Challenge {
+    /// Returns when this challenge was last updated.
+    pub fn last_updated(&self) -> DateTime<Utc> {
+        self.lastupdatedtime
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -256,7 +481,11 @@ mod tests {
             env::var("SR_PASSWORD").unwrap().as_str(),
         )
         .await?;
-        assert_eq!(env::var("SR_USERNAME").unwrap().to_ascii_lowercase(), sr.token.username.to_ascii_lowercase());
+        let token = sr.token_data().expect("PasswordProvider should cache the full Token");
+        assert_eq!(
+            env::var("SR_USERNAME").unwrap().to_ascii_lowercase(),
+            token.username.to_ascii_lowercase()
+        );
         Ok(())
     }
 
@@ -270,7 +499,7 @@ mod tests {
     #[tokio::test]
     async fn list_activity_test() -> Result<()> {
         init();
-        let sr = StarRealms::new(
+        let mut sr = StarRealms::new(
             env::var("SR_USERNAME").unwrap().as_str(),
             env::var("SR_PASSWORD").unwrap().as_str(),
         )
@@ -279,6 +508,43 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn mock_which_turn_test() -> Result<()> {
+        let mut sr = MockStarRealms::new();
+        let activity = sr.activity().await?;
+        let game = &activity.activegames[0];
+        assert!(game.is_player_one());
+        assert_eq!(game.which_turn(), "Hero");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn mock_is_finished_test() -> Result<()> {
+        let mut sr = MockStarRealms::new();
+        let activity = sr.activity().await?;
+        assert!(!activity.activegames[0].is_finished());
+        assert!(activity.finishedgames[0].is_finished());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn mock_game_state_and_submit_action_test() -> Result<()> {
+        let mut sr = MockStarRealms::new();
+        let activity = sr.activity().await?;
+        let game = &activity.activegames[0];
+        let auth = game.clientdata.get_auth(&game.clientdata.p1_name)?;
+
+        let state = sr.game_state(game.id, auth).await?;
+        assert_eq!(state.id, game.id);
+
+        let state = sr.submit_action(game.id, auth, GameAction::EndTurn).await?;
+        assert_eq!(state.id, game.id);
+
+        let unknown_game_id = game.id + 1;
+        assert!(sr.game_state(unknown_game_id, auth).await.is_err());
+        Ok(())
+    }
+
     // #[tokio::test]
     // async fn list_active_games_test() -> Result<()> {
     //     init();
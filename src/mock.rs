@@ -0,0 +1,61 @@
+use async_trait::async_trait;
+
+use crate::{Activity, Error, GameAction, GameState, Result, StarRealmsApi};
+
+const FIXTURE_ACTIVITY: &str = include_str!("../tests/fixtures/activity.json");
+
+/// An in-memory [`StarRealmsApi`] implementation that serves canned fixture data instead of
+/// calling the real whitewizardgames servers. This lets consumers (and this crate's own tests)
+/// exercise logic like [`crate::Game::which_turn`] deterministically and offline, instead of
+/// needing live `SR_USERNAME`/`SR_PASSWORD` credentials.
+#[derive(Debug, Clone)]
+pub struct MockStarRealms {
+    activity: Activity,
+}
+
+impl MockStarRealms {
+    /// Creates a mock populated with the crate's bundled fixture data.
+    pub fn new() -> Self {
+        let activity: Activity =
+            serde_json::from_str(FIXTURE_ACTIVITY).expect("fixture activity.json should be valid");
+        MockStarRealms { activity }
+    }
+}
+
+impl Default for MockStarRealms {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl StarRealmsApi for MockStarRealms {
+    async fn activity(&mut self) -> Result<Activity> {
+        Ok(self.activity.clone())
+    }
+
+    async fn game_state(&mut self, game_id: i64, _auth: isize) -> Result<GameState> {
+        self.activity
+            .activegames
+            .iter()
+            .chain(self.activity.finishedgames.iter())
+            .find(|game| game.id == game_id)
+            .map(|game| GameState {
+                id: game.id,
+                turn: 0,
+                board: serde_json::Value::Null,
+                hand: Vec::new(),
+                result: self.activity.result.clone(),
+            })
+            .ok_or_else(|| Error::InvalidAPIResponse(format!("unknown game id {game_id}")))
+    }
+
+    async fn submit_action(
+        &mut self,
+        game_id: i64,
+        auth: isize,
+        _action: GameAction,
+    ) -> Result<GameState> {
+        self.game_state(game_id, auth).await
+    }
+}